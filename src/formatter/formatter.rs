@@ -0,0 +1,212 @@
+use crate::scanner::{to_source, Token, TokenType};
+
+const INDENT: &str = "    ";
+
+// Re-emits a program one statement per line, indenting block contents and
+// normalizing spacing within each line as `to_source` does. Comments stay
+// on the line they were written on, and runs of blank lines become one.
+// Formatting the output again leaves it unchanged.
+pub fn format(tokens: &[Token]) -> String {
+    let mut formatter = Formatter {
+        tokens,
+        output: String::new(),
+        start: 0,
+        depth: 0,
+        line_depth: 0,
+    };
+    formatter.format();
+    return formatter.output;
+}
+
+struct Formatter<'a> {
+    tokens: &'a [Token],
+    output: String,
+    // First token of the line being built.
+    start: usize,
+    depth: usize,
+    // Depth when the line being built started, which is its indentation
+    // even if a `{` on it has since opened a block.
+    line_depth: usize,
+}
+
+impl Formatter<'_> {
+    fn format(&mut self) {
+        // Open parentheses, counted per brace level so a function body
+        // passed as an argument still gets one statement per line.
+        let mut parens: Vec<usize> = vec![0];
+        // One entry per open brace, set when it opened a `do` body so the
+        // closing brace keeps its `while` on the same line.
+        let mut braces: Vec<bool> = Vec::new();
+        let mut end = self.tokens.len();
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token.token_type() {
+                TokenType::EOF => {
+                    end = i;
+                    break;
+                }
+                TokenType::RightBrace => {
+                    self.end_line(i);
+                    self.depth = self.depth.saturating_sub(1);
+                }
+                TokenType::Comment if i > 0 && token.line() != end_line(&self.tokens[i - 1]) => {
+                    self.end_line(i);
+                }
+                _ => {}
+            }
+
+            if i == self.start {
+                self.line_depth = self.depth;
+            }
+
+            // Keep one blank line where the source had any.
+            let starts_line = i == self.start && token.token_type() != TokenType::RightBrace;
+            if starts_line && i > 0 && token.line() > end_line(&self.tokens[i - 1]) + 1 {
+                self.output.push('\n');
+            }
+
+            match token.token_type() {
+                TokenType::LeftParen => *parens.last_mut().unwrap() += 1,
+                TokenType::RightParen => {
+                    let count = parens.last_mut().unwrap();
+                    *count = count.saturating_sub(1);
+                }
+                TokenType::LeftBrace => {
+                    parens.push(0);
+                    let after_do = i > 0 && self.tokens[i - 1].token_type() == TokenType::Do;
+                    braces.push(after_do);
+                    self.break_after(i);
+                    self.depth += 1;
+                }
+                TokenType::RightBrace => {
+                    if parens.len() > 1 {
+                        parens.pop();
+                    }
+                    let closes_do = braces.pop().unwrap_or(false);
+                    let joined = match self.tokens.get(i + 1).map(|t| t.token_type()) {
+                        Some(TokenType::Else) => true,
+                        Some(TokenType::While) => closes_do,
+                        Some(TokenType::SemiColon | TokenType::Comma | TokenType::RightParen) => {
+                            true
+                        }
+                        _ => false,
+                    };
+                    if !joined {
+                        self.break_after(i);
+                    }
+                }
+                // The clauses of a `for` stay together, as does an `else`
+                // with the unbraced `if` body before it.
+                TokenType::SemiColon if parens.last() == Some(&0) => {
+                    let before_else = self
+                        .tokens
+                        .get(i + 1)
+                        .is_some_and(|t| t.token_type() == TokenType::Else);
+                    if !before_else {
+                        self.break_after(i);
+                    }
+                }
+                TokenType::Comment => self.end_line(i + 1),
+                _ => {}
+            }
+        }
+
+        self.end_line(end);
+    }
+
+    // Ends the line after token `i`, unless a comment follows on the same
+    // source line, in which case the comment ends it instead.
+    fn break_after(&mut self, i: usize) {
+        if let Some(next) = self.tokens.get(i + 1) {
+            if next.token_type() == TokenType::Comment && next.line() == end_line(&self.tokens[i]) {
+                return;
+            }
+        }
+
+        self.end_line(i + 1);
+    }
+
+    // Writes the tokens from `start` up to `end` as one indented line.
+    fn end_line(&mut self, end: usize) {
+        if self.start < end {
+            self.output.push_str(&INDENT.repeat(self.line_depth));
+            self.output
+                .push_str(&to_source(&self.tokens[self.start..end]));
+            self.output.push('\n');
+        }
+        self.start = end;
+    }
+}
+
+// Line the token's last character is on, for tokens such as multi-line
+// strings.
+fn end_line(token: &Token) -> u128 {
+    return token.line() + token.lexeme().matches('\n').count() as u128;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn format_source(source: &str) -> String {
+        let mut scanner = Scanner::new(source);
+        scanner.set_keep_comments(true);
+        scanner.scan_tokens();
+        return format(scanner.tokens());
+    }
+
+    #[test]
+    fn format_test() {
+        let source = "// Counts down.\nfun countdown(n){while(n>0){print n;n=n-1;}}\n\n\n\
+                      if (ready)   {countdown(3);} else {print \"not yet\";}  // fallback\n\
+                      do { x = x+1; } while (x<3);\nfor(var i=0;i<3;i=i+1) print i;\n\
+                      f(fun(){ a; b; });\nif (a) print b; else { c; }\n\
+                      if (a) { // why\n    b;\n}\nwhile (x) { if (a) { // why\n b; } }";
+
+        assert_eq!(
+            format_source(source),
+            "// Counts down.
+fun countdown(n) {
+    while (n > 0) {
+        print n;
+        n = n - 1;
+    }
+}
+
+if (ready) {
+    countdown(3);
+} else {
+    print \"not yet\";
+} // fallback
+do {
+    x = x + 1;
+} while (x < 3);
+for (var i = 0; i < 3; i = i + 1) print i;
+f(fun () {
+    a;
+    b;
+});
+if (a) print b; else {
+    c;
+}
+if (a) { // why
+    b;
+}
+while (x) {
+    if (a) { // why
+        b;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn format_idempotent_test() {
+        let source = "class A{init(){this.x=\"a\nb\";}} // multi-line\n\n\n\
+                      var f=fun(a,b){return a*-b;};print f(1,2);";
+        let once = format_source(source);
+
+        assert_eq!(format_source(&once), once);
+    }
+}
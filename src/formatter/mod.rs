@@ -0,0 +1,3 @@
+pub mod formatter;
+
+pub use self::formatter::format;
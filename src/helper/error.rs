@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Error {
+    reason: String,
+    line: u128,
+    kind: ErrorKind,
+}
+
+impl Error {
+    pub fn new(line: u128, reason: String) -> Self {
+        return Error {
+            line,
+            reason,
+            kind: ErrorKind::Error,
+        };
+    }
+
+    // Warnings are reported like errors but don't fail the run.
+    pub fn warning(line: u128, reason: String) -> Self {
+        return Error {
+            line,
+            reason,
+            kind: ErrorKind::Warning,
+        };
+    }
+
+    pub fn reason(&self) -> &str {
+        return &self.reason;
+    }
+
+    pub fn line(&self) -> u128 {
+        return self.line;
+    }
+
+    // Moves the error by a number of lines after an edit above it. Only the
+    // line field moves, so reasons must not mention line numbers.
+    pub fn shift_line(&mut self, delta: i128) {
+        self.line = (self.line as i128 + delta) as u128;
+    }
+
+    pub fn is_warning(&self) -> bool {
+        return self.kind == ErrorKind::Warning;
+    }
+}
+
+// Everything that can stop a script from loading or running, so callers
+// can tell an unreadable file apart from a program with errors.
+#[derive(Debug)]
+pub enum LoxError {
+    Io(io::Error),
+    Lox(Vec<Error>),
+}
+
+impl From<io::Error> for LoxError {
+    fn from(error: io::Error) -> Self {
+        return LoxError::Io(error);
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoxError::Io(error) => write!(f, "{}", error),
+            LoxError::Lox(errors) => write!(f, "{} error(s)", errors.len()),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+pub fn report_errors(errors: &[Error], source: &str, color: bool) {
+    print!("{}", format_errors(errors, source, color));
+}
+
+// Groups errors by line in line order, printing each offending source line
+// once followed by its messages. Errors are labelled red and warnings
+// yellow when `color` is set.
+pub fn format_errors(errors: &[Error], source: &str, color: bool) -> String {
+    let mut by_line: BTreeMap<u128, Vec<&Error>> = BTreeMap::new();
+    for error in errors {
+        by_line.entry(error.line).or_default().push(error);
+    }
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut output = String::new();
+    for (line, errors) in by_line {
+        let text = lines.get(line as usize - 1).unwrap_or(&"").trim();
+        if text.is_empty() {
+            output.push_str(&format!("[Line {} ]\n", line));
+        } else {
+            output.push_str(&format!("[Line {} ] {}\n", line, text));
+        }
+
+        for error in errors {
+            let (label, code) = match error.kind {
+                ErrorKind::Error => ("Error", "31"),
+                ErrorKind::Warning => ("Warning", "33"),
+            };
+            let label = if color {
+                format!("\x1b[{}m{}\x1b[0m", code, label)
+            } else {
+                label.to_string()
+            };
+            output.push_str(&format!("    {}: {}\n", label, error.reason));
+        }
+    }
+
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "var a = $;\nvar b = 1e999;\nprint @ #;\n";
+
+    fn errors() -> Vec<Error> {
+        return vec![
+            Error::new(3, "Unexpected Character".to_string()),
+            Error::new(1, "Unexpected Character".to_string()),
+            Error::warning(2, "Number literal is too large".to_string()),
+            Error::new(3, "Expected attribute name after '@'".to_string()),
+        ];
+    }
+
+    #[test]
+    fn format_errors_plain_test() {
+        let output = format_errors(&errors(), SOURCE, false);
+
+        assert!(!output.contains('\x1b'));
+        assert_eq!(
+            output,
+            "[Line 1 ] var a = $;
+    Error: Unexpected Character
+[Line 2 ] var b = 1e999;
+    Warning: Number literal is too large
+[Line 3 ] print @ #;
+    Error: Unexpected Character
+    Error: Expected attribute name after '@'
+"
+        );
+    }
+
+    #[test]
+    fn format_errors_color_test() {
+        let output = format_errors(&errors(), SOURCE, true);
+
+        assert!(output.contains("\x1b[31mError\x1b[0m"));
+        assert!(output.contains("\x1b[33mWarning\x1b[0m"));
+    }
+
+    #[test]
+    fn lox_error_from_io_test() {
+        let error: LoxError = io::Error::new(io::ErrorKind::NotFound, "missing").into();
+
+        assert!(matches!(error, LoxError::Io(_)));
+        assert_eq!(error.to_string(), "missing");
+    }
+}
@@ -1 +1,3 @@
-pub(crate) mod helper;
+pub(crate) mod error;
+
+pub use self::error::{format_errors, report_errors, Error, ErrorKind, LoxError};
@@ -0,0 +1,4 @@
+pub mod formatter;
+pub mod helper;
+pub mod runner;
+pub mod scanner;
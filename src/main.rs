@@ -1,51 +1,398 @@
 use std::env;
 use std::fs;
 use std::io;
+use std::io::{BufRead, IsTerminal, Write};
 use std::process;
 
-mod helper;
-mod scanner;
+use crafting_interpreters::helper::{self, Error, LoxError};
+use crafting_interpreters::runner::{check, has_errors, run};
+use crafting_interpreters::{formatter, runner, scanner};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Run,
+    Check,
+    Count,
+    Fmt,
+}
+
+// The REPL's prompts, overridden by LOX_PROMPT and LOX_CONTINUATION_PROMPT.
+// The continuation prompt is shown while an entry has an open bracket or
+// string.
+struct Prompts {
+    primary: String,
+    continuation: String,
+}
+
+impl Default for Prompts {
+    fn default() -> Self {
+        return Prompts {
+            primary: "> ".to_string(),
+            continuation: "... ".to_string(),
+        };
+    }
+}
+
+impl Prompts {
+    fn from_env() -> Self {
+        let default = Prompts::default();
+        return Prompts {
+            primary: env::var("LOX_PROMPT").unwrap_or(default.primary),
+            continuation: env::var("LOX_CONTINUATION_PROMPT").unwrap_or(default.continuation),
+        };
+    }
+}
+
+const USAGE: &str = "Usage: lox [--check | --count | --fmt] [--no-color] [script | -]";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: lox [script]");
+    let mut mode = Mode::Run;
+    let mut no_color = false;
+    let mut paths: Vec<String> = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--check" => mode = Mode::Check,
+            "--count" => mode = Mode::Count,
+            "--fmt" => mode = Mode::Fmt,
+            "--no-color" => no_color = true,
+            flag if flag.starts_with('-') && flag != "-" => {
+                eprintln!("error: unknown flag '{}'", flag);
+                eprintln!("{}", USAGE);
+                process::exit(64);
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    // Colour only when the stream diagnostics go to is a terminal, which in
+    // --fmt mode is stderr.
+    let color = !no_color
+        && if mode == Mode::Fmt {
+            io::stderr().is_terminal()
+        } else {
+            io::stdout().is_terminal()
+        };
+
+    if paths.len() > 1 {
+        println!("{}", USAGE);
+        process::exit(64);
+    } else if paths.len() == 1 {
+        exit_on_error(&paths[0], run_file(&paths[0], mode, color));
+    } else if !io::stdin().is_terminal() {
+        // Piped input is a whole program, not a session.
+        exit_on_error("-", run_file("-", mode, color));
+    } else if mode != Mode::Run {
+        println!("{}", USAGE);
         process::exit(64);
-    } else if args.len() == 2 {
-        run_file(args[1].clone());
     } else {
-        run_prompt();
+        run_prompt(
+            &mut io::stdin().lock(),
+            &mut io::stdout(),
+            color,
+            &Prompts::from_env(),
+        );
+    }
+}
+
+fn exit_on_error(file_path: &str, result: Result<(), LoxError>) {
+    match result {
+        Ok(()) => {}
+        Err(LoxError::Io(error)) => {
+            eprintln!("error: cannot read '{}': {}", file_path, error);
+            process::exit(66);
+        }
+        Err(LoxError::Lox(_)) => process::exit(65),
     }
 }
 
-fn run_file(file_path: String) {
-    let contents = fs::read_to_string(file_path).expect("Unable to read file");
-    let has_errors = run(contents);
+// Diagnostics, warnings included, are printed here; the result only says
+// whether the file could be read and whether the program had errors.
+fn run_file(file_path: &str, mode: Mode, color: bool) -> Result<(), LoxError> {
+    let contents = runner::read_source(file_path)?;
+    let errors = match mode {
+        Mode::Run => run(&contents),
+        Mode::Check => check(&contents),
+        Mode::Count => count(&contents),
+        Mode::Fmt => fmt(&contents),
+    };
 
-    if has_errors {
-        process::exit(65)
+    // In --fmt mode stdout is the formatted program, so diagnostics must
+    // not end up in a redirected source file.
+    if mode == Mode::Fmt {
+        eprint!("{}", helper::format_errors(&errors, &contents, color));
+    } else {
+        helper::report_errors(&errors, &contents, color);
+    }
+    if has_errors(&errors) {
+        return Err(LoxError::Lox(errors));
     }
+    return Ok(());
 }
 
-fn run_prompt() {
+fn run_prompt(input: &mut impl BufRead, output: &mut impl Write, color: bool, prompts: &Prompts) {
     loop {
-        print!("> ");
         let mut line = String::new();
-        let bytes = io::stdin()
-            .read_line(&mut line)
-            .expect("Unable to read line from stdin");
-        if bytes == 0 {
+        if !read_entry_line(input, output, &prompts.primary, &mut line) {
             break;
         }
 
-        run(line);
+        let entry = line.trim();
+        let (command, argument) = entry.split_once(char::is_whitespace).unwrap_or((entry, ""));
+        let source = if command == ".load" {
+            let path = argument.trim();
+            if path.is_empty() {
+                writeln!(output, "error: usage: .load <path>").expect("Unable to write error");
+                continue;
+            }
+            match fs::read_to_string(path) {
+                Ok(contents) => contents,
+                Err(error) => {
+                    writeln!(output, "error: cannot read '{}': {}", path, error)
+                        .expect("Unable to write error");
+                    continue;
+                }
+            }
+        } else {
+            while is_incomplete(&line) {
+                if !read_entry_line(input, output, &prompts.continuation, &mut line) {
+                    break;
+                }
+            }
+            line
+        };
+
+        let errors = run(&source);
+        write!(output, "{}", helper::format_errors(&errors, &source, color))
+            .expect("Unable to write errors");
+    }
+}
+
+// Shows the prompt and appends the next line of input to `line`, returning
+// false at the end of input.
+fn read_entry_line(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+    line: &mut String,
+) -> bool {
+    write!(output, "{}", prompt).expect("Unable to write prompt");
+    output.flush().expect("Unable to write prompt");
+    let bytes = input
+        .read_line(line)
+        .expect("Unable to read line from stdin");
+    return bytes != 0;
+}
+
+// A REPL entry carries on to the next line while a bracket or string is
+// still open.
+fn is_incomplete(source: &str) -> bool {
+    let mut scanner = scanner::Scanner::new(source);
+    scanner.scan_tokens();
+
+    let mut depth = 0;
+    for token in scanner.tokens() {
+        match token.token_type() {
+            scanner::TokenType::LeftParen | scanner::TokenType::LeftBrace => depth += 1,
+            scanner::TokenType::RightParen | scanner::TokenType::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    let open_string = scanner
+        .errors()
+        .iter()
+        .any(|error| error.reason().starts_with("Unterminated String"));
+    return depth > 0 || open_string;
+}
+
+// Scans only, printing the token and line totals.
+fn count(source: &str) -> Vec<Error> {
+    let mut scanner = scanner::Scanner::new(source);
+    scanner.scan_tokens();
+
+    // The trailing EOF token isn't part of the program.
+    println!(
+        "{} tokens, {} lines",
+        scanner.tokens().len() - 1,
+        source.lines().count()
+    );
+    return scanner.into_errors();
+}
+
+// Prints the formatted program, or nothing if it doesn't scan.
+fn fmt(source: &str) -> Vec<Error> {
+    let mut scanner = scanner::Scanner::new(source);
+    scanner.set_keep_comments(true);
+    scanner.scan_tokens();
+
+    if !has_errors(scanner.errors()) {
+        // The scanner skips a shebang line, so it is copied through as is.
+        if source.starts_with("#!") {
+            println!("{}", source.lines().next().unwrap_or_default());
+        }
+        print!("{}", formatter::format(scanner.tokens()));
     }
+    return scanner.into_errors();
 }
 
-fn run(source: String) -> bool {
-    let mut scanner = scanner::scanner::Scanner::new(&source);
-    let errors = scanner.scan_tokens();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::{Cursor, Read};
+    use std::rc::Rc;
+
+    fn repl(input: &str) -> String {
+        let mut output = Vec::new();
+        run_prompt(
+            &mut Cursor::new(input.as_bytes()),
+            &mut output,
+            false,
+            &Prompts::default(),
+        );
+        return String::from_utf8(output).unwrap();
+    }
 
-    helper::helper::report_errors(&errors);
-    return errors.len() != 0;
+    #[test]
+    fn flattened_scanner_path_test() {
+        let mut scanner: scanner::Scanner = scanner::Scanner::new("print 1;");
+        scanner.scan_tokens();
+
+        let tokens: &[scanner::Token] = scanner.tokens();
+        assert_eq!(tokens.len(), 4);
+        assert!(scanner::TokenType::Print.is_keyword());
+    }
+
+    #[test]
+    fn flattened_helper_path_test() {
+        let errors: Vec<helper::Error> = vec![helper::Error::warning(1, "Unused".to_string())];
+
+        assert!(!has_errors(&errors));
+        assert_eq!(
+            helper::format_errors(&errors, "var a;", false),
+            "[Line 1 ] var a;\n    Warning: Unused\n"
+        );
+    }
+
+    // Only passes written bytes on when flushed, like a line-buffered
+    // terminal waiting for a newline.
+    struct BufferedOutput {
+        pending: Vec<u8>,
+        flushed: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Write for BufferedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.pending.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed.borrow_mut().append(&mut self.pending);
+            return Ok(());
+        }
+    }
+
+    // Records what had been flushed each time input is read.
+    struct WatchedInput {
+        input: Cursor<&'static [u8]>,
+        flushed: Rc<RefCell<Vec<u8>>>,
+        seen: Vec<String>,
+    }
+
+    impl Read for WatchedInput {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            return self.input.read(buf);
+        }
+    }
+
+    impl BufRead for WatchedInput {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            let flushed = String::from_utf8(self.flushed.borrow().clone()).unwrap();
+            self.seen.push(flushed);
+            return self.input.fill_buf();
+        }
+
+        fn consume(&mut self, amount: usize) {
+            self.input.consume(amount);
+        }
+    }
+
+    #[test]
+    fn repl_flushes_prompt_before_read_test() {
+        let flushed = Rc::new(RefCell::new(Vec::new()));
+        let mut input = WatchedInput {
+            input: Cursor::new(b"var $a;\n"),
+            flushed: Rc::clone(&flushed),
+            seen: Vec::new(),
+        };
+        let mut output = BufferedOutput {
+            pending: Vec::new(),
+            flushed: Rc::clone(&flushed),
+        };
+
+        run_prompt(&mut input, &mut output, false, &Prompts::default());
+
+        assert_eq!(
+            input.seen,
+            vec![
+                "> ".to_string(),
+                "> [Line 1 ] var $a;\n    Error: Unexpected Character\n> ".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn repl_configured_prompts_test() {
+        let prompts = Prompts {
+            primary: "lox> ".to_string(),
+            continuation: "..| ".to_string(),
+        };
+        let mut output = Vec::new();
+        run_prompt(
+            &mut Cursor::new("fun f() {\n  print \"a\nb\";\n}\nvar $x;\n".as_bytes()),
+            &mut output,
+            false,
+            &prompts,
+        );
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "lox> ..| ..| ..| lox> [Line 1 ] var $x;\n    Error: Unexpected Character\nlox> "
+        );
+    }
+
+    #[test]
+    fn repl_load_test() {
+        let path = env::temp_dir().join(format!("lox_repl_load_{}.lox", process::id()));
+        fs::write(&path, "fun greet() {\n  print \"hi\";\n}\nvar $broken;\n").unwrap();
+
+        let output = repl(&format!(".load {}\nvar a = 1;\n", path.display()));
+
+        assert_eq!(
+            output,
+            "> [Line 4 ] var $broken;\n    Error: Unexpected Character\n> > "
+        );
+    }
+
+    #[test]
+    fn repl_load_without_path_test() {
+        assert_eq!(
+            repl(".load\n.load   \n"),
+            "> error: usage: .load <path>\n> error: usage: .load <path>\n> "
+        );
+    }
+
+    #[test]
+    fn repl_load_missing_file_test() {
+        let path = env::temp_dir().join("lox_repl_load_missing.lox");
+
+        let output = repl(&format!(".load {}\nvar $a;\n", path.display()));
+
+        assert!(output.starts_with(&format!("> error: cannot read '{}'", path.display())));
+        assert!(output.ends_with("> [Line 1 ] var $a;\n    Error: Unexpected Character\n> "));
+    }
 }
@@ -0,0 +1,3 @@
+pub mod runner;
+
+pub use self::runner::{check, check_file, has_errors, read_source, run, run_file};
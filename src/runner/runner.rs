@@ -0,0 +1,94 @@
+use std::fs;
+use std::io;
+
+use crate::helper::{Error, LoxError};
+use crate::scanner::Scanner;
+
+// Reads a whole script; `-` reads it from stdin.
+pub fn read_source(path: &str) -> Result<String, LoxError> {
+    if path == "-" {
+        return Ok(io::read_to_string(io::stdin())?);
+    }
+
+    return Ok(fs::read_to_string(path)?);
+}
+
+// Loads and runs a script. A file that can't be read is `LoxError::Io`, and
+// a program with errors is `LoxError::Lox` holding every diagnostic,
+// warnings included.
+pub fn run_file(path: &str) -> Result<(), LoxError> {
+    let source = read_source(path)?;
+    return into_result(run(&source));
+}
+
+// As `run_file`, without executing the program.
+pub fn check_file(path: &str) -> Result<(), LoxError> {
+    let source = read_source(path)?;
+    return into_result(check(&source));
+}
+
+// Only the static phases exist so far; execution follows `check` here
+// once there is an interpreter.
+pub fn run(source: &str) -> Vec<Error> {
+    let errors = check(source);
+    return errors;
+}
+
+// Runs every static phase over the source without executing it.
+pub fn check(source: &str) -> Vec<Error> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    return scanner.into_errors();
+}
+
+// Warnings are reported but don't fail the run.
+pub fn has_errors(errors: &[Error]) -> bool {
+    return errors.iter().any(|error| !error.is_warning());
+}
+
+fn into_result(errors: Vec<Error>) -> Result<(), LoxError> {
+    if has_errors(&errors) {
+        return Err(LoxError::Lox(errors));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn write_script(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("lox_runner_{}_{}", process::id(), name));
+        fs::write(&path, contents).unwrap();
+        return path.to_str().unwrap().to_string();
+    }
+
+    #[test]
+    fn run_file_missing_path_test() {
+        let path = env::temp_dir().join("lox_runner_missing.lox");
+
+        let result = run_file(path.to_str().unwrap());
+
+        assert!(matches!(result, Err(LoxError::Io(_))));
+    }
+
+    #[test]
+    fn check_file_lox_errors_test() {
+        let path = write_script("errors.lox", "var $a;\nvar b = 1e;\n");
+
+        match check_file(&path) {
+            Err(LoxError::Lox(errors)) => assert_eq!(errors.len(), 1),
+            _ => panic!("expected Lox errors"),
+        }
+    }
+
+    #[test]
+    fn run_file_warnings_only_test() {
+        let path = write_script("warnings.lox", &format!("var a = 1{};\n", "0".repeat(400)));
+
+        assert!(check(&read_source(&path).unwrap())[0].is_warning());
+        assert!(run_file(&path).is_ok());
+    }
+}
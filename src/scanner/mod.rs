@@ -1 +1,3 @@
 pub mod scanner;
+
+pub use self::scanner::{to_source, Scanner, ScannerBuilder, Token, TokenStream, TokenType};
@@ -1,5 +1,14 @@
-use crate::helper::helper::Error;
+use crate::helper::Error;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+
+const DEFAULT_MAX_STRING_LENGTH: usize = 1024 * 1024;
+const DEFAULT_MAX_NUMBER_DIGITS: usize = 1024;
+
+// Built once and shared by every scanner using the standard spellings.
+static KEYWORDS: LazyLock<HashMap<&'static str, TokenType>> = LazyLock::new(default_keywords);
 
 pub struct Scanner {
     source: Vec<char>,
@@ -7,12 +16,31 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: u128,
-    keywords: HashMap<&'static str, TokenType>,
+    // Line the token being scanned began on, so multi-line tokens report
+    // where they started.
+    start_line: u128,
+    // Line the most recent token ended on, which is where EOF is placed.
+    last_line: u128,
+    // Char offset at which each line begins, for `line_of`.
+    line_starts: Vec<usize>,
+    // One per token, for `rescan_range`.
+    checkpoints: Vec<Checkpoint>,
+    keywords: Cow<'static, HashMap<&'static str, TokenType>>,
     errors: Vec<Error>,
+    case_insensitive: bool,
+    // One entry per open `${`, counting the `{` nested inside it so the
+    // matching `}` resumes the string instead of closing a block.
+    interpolations: Vec<usize>,
+    max_string_length: usize,
+    max_number_digits: usize,
+    keep_comments: bool,
+    comment_prefix: Vec<char>,
+    warn_mixed_indentation: bool,
+    decimal_separator: char,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-enum TokenType {
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
@@ -23,6 +51,7 @@ enum TokenType {
     Minus,
     Plus,
     SemiColon,
+    Colon,
     Slash,
     Star,
 
@@ -35,106 +64,832 @@ enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
 
     // Literals.
     Identifier,
     String,
+    // A string segment that ends in `${`, followed by the tokens of the
+    // embedded expression and then the rest of the string.
+    Interpolation,
     Number,
+    // `@name` annotations such as `@test` for the following declaration.
+    Attribute,
 
     // Keywords.
     And,
+    Case,
     Class,
+    Default,
+    Do,
     Else,
     False,
     Fun,
     For,
     If,
+    Is,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
     True,
     Var,
     While,
 
+    // Trivia, only emitted when comments are kept.
+    Comment,
+
     EOF,
 }
 
+impl TokenType {
+    pub fn is_keyword(&self) -> bool {
+        return matches!(
+            self,
+            TokenType::And
+                | TokenType::Case
+                | TokenType::Class
+                | TokenType::Default
+                | TokenType::Do
+                | TokenType::Else
+                | TokenType::False
+                | TokenType::Fun
+                | TokenType::For
+                | TokenType::If
+                | TokenType::Is
+                | TokenType::Nil
+                | TokenType::Or
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Super
+                | TokenType::Switch
+                | TokenType::This
+                | TokenType::True
+                | TokenType::Var
+                | TokenType::While
+        );
+    }
+
+    // Tokens that stand for a value on their own, including the `true`,
+    // `false` and `nil` keywords but not identifiers.
+    pub fn is_literal(&self) -> bool {
+        return matches!(
+            self,
+            TokenType::String
+                | TokenType::Interpolation
+                | TokenType::Number
+                | TokenType::True
+                | TokenType::False
+                | TokenType::Nil
+        );
+    }
+
+    pub fn is_binary_operator(&self) -> bool {
+        return matches!(
+            self,
+            TokenType::Minus
+                | TokenType::Plus
+                | TokenType::Slash
+                | TokenType::Star
+                | TokenType::StarStar
+                | TokenType::BangEqual
+                | TokenType::EqualEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::And
+                | TokenType::Or
+                | TokenType::Is
+        );
+    }
+
+    // Keywords that begin a declaration or statement, where the parser can
+    // resynchronize after an error.
+    pub fn is_statement_start(&self) -> bool {
+        return matches!(
+            self,
+            TokenType::Class
+                | TokenType::Do
+                | TokenType::For
+                | TokenType::Fun
+                | TokenType::If
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Switch
+                | TokenType::Var
+                | TokenType::While
+        );
+    }
+
+    // The fixed spelling of operators, punctuation and the standard
+    // keywords; `None` for tokens whose text comes from the source.
+    pub fn text(&self) -> Option<&'static str> {
+        let text = match self {
+            TokenType::LeftParen => "(",
+            TokenType::RightParen => ")",
+            TokenType::LeftBrace => "{",
+            TokenType::RightBrace => "}",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::SemiColon => ";",
+            TokenType::Colon => ":",
+            TokenType::Slash => "/",
+            TokenType::Star => "*",
+            TokenType::Bang => "!",
+            TokenType::BangEqual => "!=",
+            TokenType::Equal => "=",
+            TokenType::EqualEqual => "==",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::StarStar => "**",
+            TokenType::And => "and",
+            TokenType::Case => "case",
+            TokenType::Class => "class",
+            TokenType::Default => "default",
+            TokenType::Do => "do",
+            TokenType::Else => "else",
+            TokenType::False => "false",
+            TokenType::Fun => "fun",
+            TokenType::For => "for",
+            TokenType::If => "if",
+            TokenType::Is => "is",
+            TokenType::Nil => "nil",
+            TokenType::Or => "or",
+            TokenType::Print => "print",
+            TokenType::Return => "return",
+            TokenType::Super => "super",
+            TokenType::Switch => "switch",
+            TokenType::This => "this",
+            TokenType::True => "true",
+            TokenType::Var => "var",
+            TokenType::While => "while",
+            _ => return None,
+        };
+
+        return Some(text);
+    }
+
+    // Tokens after which a `-` is binary rather than a negation.
+    fn ends_operand(&self) -> bool {
+        return matches!(
+            self,
+            TokenType::Identifier
+                | TokenType::String
+                | TokenType::Number
+                | TokenType::True
+                | TokenType::False
+                | TokenType::Nil
+                | TokenType::This
+                | TokenType::RightParen
+        );
+    }
+}
+
 #[derive(PartialEq, Debug)]
-enum Literal {
+pub enum Literal {
     Identifier(String),
     String(String),
     Number(f64),
 }
 
-struct Token {
+pub struct Token {
     token_type: TokenType,
     lexeme: String,
     literal: Option<Literal>,
     line: u128,
+    // Char offset of the token's first character in the source.
+    offset: usize,
+}
+
+// The scanner's output, for consumers that want `for token in stream` and
+// iterator adapters rather than a slice.
+pub struct TokenStream(Vec<Token>);
+
+impl TokenStream {
+    pub fn iter(&self) -> std::slice::Iter<'_, Token> {
+        return self.0.iter();
+    }
+
+    // Drops the `Comment` tokens a scanner keeping comments emits.
+    pub fn filter_comments(self) -> TokenStream {
+        return TokenStream(
+            self.0
+                .into_iter()
+                .filter(|token| token.token_type != TokenType::Comment)
+                .collect(),
+        );
+    }
+}
+
+impl From<Vec<Token>> for TokenStream {
+    fn from(tokens: Vec<Token>) -> Self {
+        return TokenStream(tokens);
+    }
+}
+
+impl IntoIterator for TokenStream {
+    type Item = Token;
+    type IntoIter = std::vec::IntoIter<Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.0.into_iter();
+    }
+}
+
+impl<'a> IntoIterator for &'a TokenStream {
+    type Item = &'a Token;
+    type IntoIter = std::slice::Iter<'a, Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.0.iter();
+    }
+}
+
+// Scanner state at the end of the step that produced a token, so a rescan
+// can resume from, or rejoin, that point.
+struct Checkpoint {
+    errors: usize,
+    interpolations: usize,
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Identifier(name) => write!(f, "{}", name),
+            Literal::String(string) => write!(f, "{:?}", string),
+            // Shortest form that round-trips, with no `.0` on integral values.
+            Literal::Number(number) => write!(f, "{}", number),
+        }
+    }
+}
+
+// Renders one token per line as `Type "lexeme" literal @line`, leaving out
+// the literal when the token has none.
+pub fn dump_tokens(tokens: &[Token]) -> String {
+    let mut dump = String::new();
+    for token in tokens {
+        dump.push_str(&format!("{:?} {:?}", token.token_type, token.lexeme));
+        if let Some(literal) = &token.literal {
+            dump.push_str(&format!(" {}", literal));
+        }
+        dump.push_str(&format!(" @{}\n", token.line));
+    }
+
+    return dump;
+}
+
+// Renders the token's canonical source text: the standard spelling for
+// operators and keywords, `//` comments, `.` as the decimal point, and the
+// lexeme as written otherwise.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(text) = self.token_type.text() {
+            return write!(f, "{}", text);
+        }
+
+        match (&self.token_type, &self.literal) {
+            (TokenType::Comment, Some(Literal::String(text))) => write!(f, "//{}", text),
+            (TokenType::Number, _) => {
+                let number: String = self
+                    .lexeme
+                    .chars()
+                    .map(|c| if c.is_ascii_digit() { c } else { '.' })
+                    .collect();
+                write!(f, "{}", number)
+            }
+            _ => write!(f, "{}", self.lexeme),
+        }
+    }
+}
+
+// Rebuilds source from tokens in a normalized form. Tokens are separated by
+// single spaces except before `;`, `,`, `.` and `)`, after `(`, `.` and
+// unary `-` or `!`, between a callee and its `(`, and just inside `${...}`.
+// Each comment ends its line. Whitespace and newlines otherwise are not
+// preserved.
+pub fn to_source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    let mut previous: Option<&Token> = None;
+    let mut after_unary = false;
+    for token in tokens {
+        if token.token_type == TokenType::EOF {
+            break;
+        }
+
+        if let Some(previous) = previous {
+            if previous.token_type == TokenType::Comment {
+                source.push('\n');
+            } else if !after_unary && spaced(previous, token) {
+                source.push(' ');
+            }
+        }
+        source.push_str(&token.to_string());
+
+        after_unary = match token.token_type {
+            TokenType::Bang => true,
+            TokenType::Minus => !previous.is_some_and(|p| p.token_type.ends_operand()),
+            _ => false,
+        };
+        previous = Some(token);
+    }
+
+    return source;
+}
+
+fn spaced(previous: &Token, token: &Token) -> bool {
+    // The rest of a string after an interpolated expression starts at `}`.
+    let resumes_string = matches!(
+        token.token_type,
+        TokenType::String | TokenType::Interpolation
+    ) && token.lexeme.starts_with('}');
+    if resumes_string {
+        return false;
+    }
+
+    if matches!(
+        token.token_type,
+        TokenType::SemiColon | TokenType::Comma | TokenType::Dot | TokenType::RightParen
+    ) {
+        return false;
+    }
+
+    if matches!(
+        previous.token_type,
+        TokenType::LeftParen | TokenType::Dot | TokenType::Interpolation
+    ) {
+        return false;
+    }
+
+    let call = token.token_type == TokenType::LeftParen
+        && matches!(
+            previous.token_type,
+            TokenType::Identifier | TokenType::RightParen
+        );
+    return !call;
+}
+
+impl Token {
+    pub fn token_type(&self) -> TokenType {
+        return self.token_type;
+    }
+
+    pub fn lexeme(&self) -> &str {
+        return &self.lexeme;
+    }
+
+    pub fn line(&self) -> u128 {
+        return self.line;
+    }
+
+    // Char offset just past the token's last character.
+    fn end(&self) -> usize {
+        return self.offset + self.lexeme.chars().count();
+    }
+}
+
+fn line_starts(source: &[char]) -> Vec<usize> {
+    let mut line_starts = vec![0];
+    for (offset, c) in source.iter().enumerate() {
+        if *c == '\n' {
+            line_starts.push(offset + 1);
+        }
+    }
+
+    return line_starts;
+}
+
+// The standard Lox keyword spellings.
+pub fn default_keywords() -> HashMap<&'static str, TokenType> {
+    return HashMap::from([
+        ("and", TokenType::And),
+        ("case", TokenType::Case),
+        ("class", TokenType::Class),
+        ("default", TokenType::Default),
+        ("do", TokenType::Do),
+        ("else", TokenType::Else),
+        ("false", TokenType::False),
+        ("for", TokenType::For),
+        ("fun", TokenType::Fun),
+        ("if", TokenType::If),
+        ("is", TokenType::Is),
+        ("nil", TokenType::Nil),
+        ("or", TokenType::Or),
+        ("print", TokenType::Print),
+        ("return", TokenType::Return),
+        ("super", TokenType::Super),
+        ("switch", TokenType::Switch),
+        ("this", TokenType::This),
+        ("true", TokenType::True),
+        ("var", TokenType::Var),
+        ("while", TokenType::While),
+    ]);
+}
+
+// Collects scanner options once so several scanners can be built with the
+// same configuration. `Scanner::new` stays the default-config shortcut.
+#[derive(Clone)]
+pub struct ScannerBuilder {
+    keywords: Option<HashMap<&'static str, TokenType>>,
+    case_insensitive: bool,
+    max_string_length: usize,
+    max_number_digits: usize,
+    keep_comments: bool,
+    comment_prefix: Vec<char>,
+    warn_mixed_indentation: bool,
+    decimal_separator: char,
+}
+
+impl Default for ScannerBuilder {
+    fn default() -> Self {
+        return ScannerBuilder::new();
+    }
+}
+
+impl ScannerBuilder {
+    pub fn new() -> Self {
+        return ScannerBuilder {
+            keywords: None,
+            case_insensitive: false,
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            max_number_digits: DEFAULT_MAX_NUMBER_DIGITS,
+            keep_comments: false,
+            comment_prefix: vec!['/', '/'],
+            warn_mixed_indentation: false,
+            decimal_separator: '.',
+        };
+    }
+
+    // Each option matches the `Scanner` setter of the same name.
+    pub fn keywords(mut self, keywords: HashMap<&'static str, TokenType>) -> Self {
+        self.keywords = Some(keywords);
+        return self;
+    }
+
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        return self;
+    }
+
+    pub fn max_string_length(mut self, max_string_length: usize) -> Self {
+        self.max_string_length = max_string_length;
+        return self;
+    }
+
+    pub fn max_number_digits(mut self, max_number_digits: usize) -> Self {
+        self.max_number_digits = max_number_digits;
+        return self;
+    }
+
+    pub fn keep_comments(mut self, keep_comments: bool) -> Self {
+        self.keep_comments = keep_comments;
+        return self;
+    }
+
+    pub fn comment_prefix(mut self, comment_prefix: &str) -> Self {
+        self.comment_prefix = comment_prefix.chars().collect();
+        return self;
+    }
+
+    pub fn warn_mixed_indentation(mut self, warn_mixed_indentation: bool) -> Self {
+        self.warn_mixed_indentation = warn_mixed_indentation;
+        return self;
+    }
+
+    pub fn decimal_separator(mut self, decimal_separator: char) -> Self {
+        self.decimal_separator = decimal_separator;
+        return self;
+    }
+
+    pub fn build(&self, source: &str) -> Scanner {
+        let keywords = match &self.keywords {
+            Some(keywords) => Cow::Owned(keywords.clone()),
+            None => Cow::Borrowed(&*KEYWORDS),
+        };
+
+        let mut scanner = Scanner::from_keywords(source, keywords);
+        scanner.case_insensitive = self.case_insensitive;
+        scanner.max_string_length = self.max_string_length;
+        scanner.max_number_digits = self.max_number_digits;
+        scanner.keep_comments = self.keep_comments;
+        scanner.comment_prefix = self.comment_prefix.clone();
+        scanner.warn_mixed_indentation = self.warn_mixed_indentation;
+        scanner.decimal_separator = self.decimal_separator;
+        return scanner;
+    }
 }
 
 impl Scanner {
-    pub fn new(source: &String) -> Self {
+    pub fn new(source: &str) -> Self {
+        return Scanner::from_keywords(source, Cow::Borrowed(&KEYWORDS));
+    }
+
+    // Scans with a custom keyword spelling table, e.g. `function` for `Fun`.
+    pub fn with_keywords(source: &str, keywords: HashMap<&'static str, TokenType>) -> Self {
+        return Scanner::from_keywords(source, Cow::Owned(keywords));
+    }
+
+    fn from_keywords(
+        source: &str,
+        keywords: Cow<'static, HashMap<&'static str, TokenType>>,
+    ) -> Self {
+        let source: Vec<char> = source.chars().collect();
+        let line_starts = line_starts(&source);
+
         return Scanner {
-            source: source.chars().collect(),
+            source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
-            keywords: HashMap::from([
-                ("and", TokenType::And),
-                ("class", TokenType::Class),
-                ("else", TokenType::Else),
-                ("false", TokenType::False),
-                ("for", TokenType::For),
-                ("fun", TokenType::Fun),
-                ("if", TokenType::If),
-                ("nil", TokenType::Nil),
-                ("or", TokenType::Or),
-                ("print", TokenType::Print),
-                ("return", TokenType::Return),
-                ("super", TokenType::Super),
-                ("this", TokenType::This),
-                ("true", TokenType::True),
-                ("var", TokenType::Var),
-                ("while", TokenType::While),
-            ]),
+            start_line: 1,
+            last_line: 1,
+            line_starts,
+            checkpoints: Vec::new(),
+            keywords,
             errors: Vec::new(),
+            case_insensitive: false,
+            interpolations: Vec::new(),
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            max_number_digits: DEFAULT_MAX_NUMBER_DIGITS,
+            keep_comments: false,
+            comment_prefix: vec!['/', '/'],
+            warn_mixed_indentation: false,
+            decimal_separator: '.',
         };
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Error> {
+    // Matches keywords regardless of case (`VAR`, `If`) while identifiers
+    // keep their original spelling.
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    // Longer string literals are reported as errors instead of being built.
+    pub fn set_max_string_length(&mut self, max_string_length: usize) {
+        self.max_string_length = max_string_length;
+    }
+
+    pub fn set_max_number_digits(&mut self, max_number_digits: usize) {
+        self.max_number_digits = max_number_digits;
+    }
+
+    // Emits comments as `Comment` tokens instead of discarding them, for
+    // formatters and doc tools. Off by default so the parser never sees them.
+    pub fn set_keep_comments(&mut self, keep_comments: bool) {
+        self.keep_comments = keep_comments;
+    }
+
+    // Changes what starts a line comment, `//` by default. An empty prefix
+    // disables line comments.
+    pub fn set_comment_prefix(&mut self, comment_prefix: &str) {
+        self.comment_prefix = comment_prefix.chars().collect();
+    }
+
+    // Opt-in lint warning about lines indented with both tabs and spaces.
+    pub fn set_warn_mixed_indentation(&mut self, warn_mixed_indentation: bool) {
+        self.warn_mixed_indentation = warn_mixed_indentation;
+    }
+
+    // Sets the character between a number's integer and fractional digits.
+    // With `,` only a comma directly between digits is a decimal point, so
+    // `3,14` is one number while `3, 14` is still two arguments.
+    pub fn set_decimal_separator(&mut self, decimal_separator: char) {
+        self.decimal_separator = decimal_separator;
+    }
+
+    pub fn scan_tokens(&mut self) -> &[Error] {
+        self.check_indentation();
+        while !self.is_at_end() {
+            self.scan_step();
+        }
+
+        self.finish();
+        return &self.errors;
+    }
+
+    // Applies an edit replacing `edit_len` chars at `edit_start` with
+    // `new_text` to an already scanned source. Tokens before the edit are
+    // kept, scanning resumes from the last clean token boundary before it,
+    // and tokens after it are reused (shifted) as soon as the scanner is back
+    // in the state the original scan had at the same point.
+    pub fn rescan_range(&mut self, edit_start: usize, edit_len: usize, new_text: &str) -> &[Token] {
+        let edit_start = edit_start.min(self.source.len());
+        let edit_end = (edit_start + edit_len).min(self.source.len());
+        let new_text: Vec<char> = new_text.chars().collect();
+        let new_edit_end = edit_start + new_text.len();
+        let delta = new_text.len() as isize - (edit_end - edit_start) as isize;
+        let line_delta = new_text.iter().filter(|c| **c == '\n').count() as i128
+            - self.source[edit_start..edit_end]
+                .iter()
+                .filter(|c| **c == '\n')
+                .count() as i128;
+
+        // Restart after the last token that ends clear of the edit, leaving
+        // room for the scanner's lookahead, and outside of any string
+        // interpolation.
+        let lookahead = self.comment_prefix.len().max(2);
+        let mut restart = self
+            .tokens
+            .iter()
+            .position(|token| token.end() + lookahead > edit_start)
+            .unwrap_or(self.tokens.len());
+        while restart > 0 && self.checkpoints[restart - 1].interpolations > 0 {
+            restart -= 1;
+        }
+
+        let mut old_tokens = self.tokens.split_off(restart);
+        old_tokens.pop();
+        let mut old_checkpoints = self.checkpoints.split_off(restart);
+        old_checkpoints.pop();
+        let kept_errors = self.checkpoints.last().map_or(0, |c| c.errors);
+        let mut old_errors = self.errors.split_off(kept_errors);
+
+        self.current = self.tokens.last().map_or(0, |token| token.end());
+        self.line = self.line_of(self.current);
+        self.last_line = if self.tokens.is_empty() { 1 } else { self.line };
+        self.interpolations.clear();
+
+        self.source.splice(edit_start..edit_end, new_text);
+        self.line_starts = line_starts(&self.source);
+
+        if self.current == 0 {
+            self.check_indentation();
+        }
         while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token()
+            self.scan_step();
+
+            if self.current < new_edit_end || !self.interpolations.is_empty() {
+                continue;
+            }
+
+            // The original scan ended a token at the same point with no
+            // open interpolation, so everything after it scans identically.
+            let old_current = (self.current as isize - delta) as usize;
+            let Ok(rejoin) = old_tokens.binary_search_by_key(&old_current, |token| token.end())
+            else {
+                continue;
+            };
+            if old_checkpoints[rejoin].interpolations > 0 {
+                continue;
+            }
+
+            // The reused errors include any `finish` reported, which is
+            // right because the old scan reached the same end from the same
+            // state.
+            let errors_base = old_checkpoints[rejoin].errors;
+            let errors_shift = self.errors.len() as isize - errors_base as isize;
+            for mut error in old_errors.drain(errors_base - kept_errors..) {
+                error.shift_line(line_delta);
+                self.errors.push(error);
+            }
+            for (mut token, checkpoint) in old_tokens
+                .drain(rejoin + 1..)
+                .zip(old_checkpoints.drain(rejoin + 1..))
+            {
+                token.offset = (token.offset as isize + delta) as usize;
+                token.line = (token.line as i128 + line_delta) as u128;
+                self.last_line = self.line_of(token.end());
+                self.tokens.push(token);
+                self.checkpoints.push(Checkpoint {
+                    errors: (checkpoint.errors as isize + errors_shift) as usize,
+                    interpolations: checkpoint.interpolations,
+                });
+            }
+
+            self.current = self.source.len();
+            self.line = self.line_of(self.current);
+            self.add_eof();
+            return &self.tokens;
+        }
+
+        self.finish();
+        return &self.tokens;
+    }
+
+    fn scan_step(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+
+        let token_count = self.tokens.len();
+        self.scan_token();
+        if self.tokens.len() > token_count {
+            self.checkpoints.push(Checkpoint {
+                errors: self.errors.len(),
+                interpolations: self.interpolations.len(),
+            });
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.interpolations.is_empty() {
+            self.errors.push(Error::new(
+                self.line,
+                "Unterminated String interpolation".to_string(),
+            ));
         }
 
+        self.add_eof();
+    }
+
+    fn add_eof(&mut self) {
+        // EOF sits on the last line that produced a token rather than after
+        // any trailing newlines or comments, so "at end" errors point at code.
         self.tokens.push(Token {
             token_type: TokenType::EOF,
             lexeme: "".to_string(),
             literal: None,
-            line: self.line,
+            line: self.last_line,
+            offset: self.source.len(),
         });
+        self.checkpoints.push(Checkpoint {
+            errors: self.errors.len(),
+            interpolations: 0,
+        });
+    }
+
+    pub fn errors(&self) -> &[Error] {
+        return &self.errors;
+    }
 
-        return self.errors.clone();
+    pub fn into_errors(self) -> Vec<Error> {
+        return self.errors;
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        return &self.tokens;
+    }
+
+    pub fn into_tokens(self) -> TokenStream {
+        return TokenStream(self.tokens);
+    }
+
+    pub fn line(&self) -> u128 {
+        return self.line;
+    }
+
+    // Maps a char offset into the source to its 1-based line. Offsets past
+    // the end belong to the last line.
+    pub fn line_of(&self, char_offset: usize) -> u128 {
+        return self
+            .line_starts
+            .partition_point(|&line_start| line_start <= char_offset) as u128;
+    }
+
+    pub fn source(&self) -> &[char] {
+        return &self.source;
+    }
+
+    // The source between two char offsets, clamped to the source so an
+    // out-of-range or reversed range gives a shorter or empty string.
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        let end = end.min(self.source.len());
+        let start = start.min(end);
+        return self.source[start..end].iter().collect();
     }
 
     fn scan_token(&mut self) {
+        if self.at_comment_prefix() {
+            self.comment();
+            return;
+        }
+
         let c = self.advance();
         match c {
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    if let Err(error) = self.string() {
+                        self.errors.push(error);
+                    }
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
             '-' => self.add_token(TokenType::Minus),
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::SemiColon),
-            '*' => self.add_token(TokenType::Star),
+            ':' => self.add_token(TokenType::Colon),
 
             '!' => {
                 let matches_eq = self.matches('=');
@@ -169,24 +924,46 @@ impl Scanner {
                 })
             }
 
-            '/' => {
-                let matches_commnet = self.matches('/');
-                if matches_commnet {
+            '*' => {
+                let matches_star = self.matches('*');
+                self.add_token(if matches_star {
+                    TokenType::StarStar
+                } else {
+                    TokenType::Star
+                })
+            }
+
+            '/' => self.add_token(TokenType::Slash),
+
+            ' ' | '\r' | '\t' => (),
+            '\n' => {
+                self.line += 1;
+                self.check_indentation();
+            }
+
+            '@' => self.attribute(),
+
+            '#' => {
+                if self.start == 0 && self.matches('!') {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
                 } else {
-                    self.add_token(TokenType::Slash);
+                    self.errors
+                        .push(Error::new(self.line, "Unexpected Character".to_string()));
                 }
             }
 
-            ' ' | '\r' | 't' => (),
-            '\n' => self.line += 1,
-
-            '"' => self.string(),
+            '"' => {
+                if let Err(error) = self.string() {
+                    self.errors.push(error);
+                }
+            }
             _ => {
-                if c.is_digit(10) {
-                    self.number();
+                if c.is_ascii_digit() {
+                    if let Err(error) = self.number() {
+                        self.errors.push(error);
+                    }
                 } else if self.is_alpha(c) {
                     self.identifier();
                 } else {
@@ -197,6 +974,47 @@ impl Scanner {
         }
     }
 
+    // Looks ahead over the leading whitespace of the line starting at
+    // `current` without consuming it.
+    fn check_indentation(&mut self) {
+        if !self.warn_mixed_indentation {
+            return;
+        }
+
+        let indentation = self.source[self.current..]
+            .iter()
+            .take_while(|c| **c == ' ' || **c == '\t');
+        let (mut tabs, mut spaces) = (false, false);
+        for c in indentation {
+            tabs |= *c == '\t';
+            spaces |= *c == ' ';
+        }
+
+        if tabs && spaces {
+            self.errors.push(Error::warning(
+                self.line,
+                "Mixed tabs and spaces in indentation".to_string(),
+            ));
+        }
+    }
+
+    fn at_comment_prefix(&self) -> bool {
+        return !self.comment_prefix.is_empty()
+            && self.source[self.current..].starts_with(&self.comment_prefix);
+    }
+
+    fn comment(&mut self) {
+        self.current += self.comment_prefix.len();
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+
+        if self.keep_comments {
+            let text = self.slice(self.start + self.comment_prefix.len(), self.current);
+            self.add_token_literal(TokenType::Comment, Some(Literal::String(text)));
+        }
+    }
+
     fn advance(&mut self) -> char {
         let result = self.source[self.current];
         self.current += 1;
@@ -208,14 +1026,14 @@ impl Scanner {
     }
 
     fn add_token_literal(&mut self, token: TokenType, literal: Option<Literal>) {
-        let text = &self.source[self.start..self.current];
-
         self.tokens.push(Token {
             token_type: token,
-            lexeme: text.iter().cloned().collect(),
+            lexeme: self.slice(self.start, self.current),
             literal,
-            line: self.line,
+            line: self.start_line,
+            offset: self.start,
         });
+        self.last_line = self.line;
     }
 
     fn matches(&mut self, expected: char) -> bool {
@@ -243,54 +1061,190 @@ impl Scanner {
         return self.source[self.current + 1];
     }
 
-    fn string(&mut self) {
+    fn string(&mut self) -> Result<(), Error> {
+        let mut string = String::new();
+        let mut length = 0;
+        // A bad escape doesn't end the string, so scanning carries on to the
+        // closing quote and reports only the first.
+        let mut escape_error = None;
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
                 self.line += 1;
             }
 
-            self.advance();
+            let mut c = self.advance();
+            if c == '\\' && self.peek() == '$' {
+                c = self.advance();
+            } else if c == '\\' && matches!(self.peek(), 'x' | 'u') {
+                match self.escape() {
+                    Ok(escaped) => c = escaped,
+                    Err(error) => {
+                        escape_error.get_or_insert(error);
+                        continue;
+                    }
+                }
+            } else if c == '$' && self.matches('{') {
+                // Open the interpolation even on error so the rest of the
+                // string isn't scanned as code.
+                self.interpolations.push(0);
+                if let Some(error) = escape_error {
+                    return Err(error);
+                }
+                return self.add_string_token(TokenType::Interpolation, string, length);
+            }
+
+            length += 1;
+            if length <= self.max_string_length {
+                string.push(c);
+            }
+        }
+
+        // Reported where the string opened, which is the line to fix.
+        if self.is_at_end() {
+            return Err(Error::new(
+                self.start_line,
+                "Unterminated String".to_string(),
+            ));
         }
 
         self.advance();
 
-        let string = self.source[self.start + 1..self.current - 1]
-            .iter()
-            .collect();
-        self.add_token_literal(TokenType::String, Some(Literal::String(string)));
+        if let Some(error) = escape_error {
+            return Err(error);
+        }
+        return self.add_string_token(TokenType::String, string, length);
+    }
+
+    // Decodes `\xNN`, two hex digits for a character up to U+00FF, or
+    // `\u{N...}`, one to six hex digits for any Unicode scalar value. The
+    // backslash has already been consumed.
+    fn escape(&mut self) -> Result<char, Error> {
+        let start = self.current - 1;
+        let value = if self.advance() == 'x' {
+            let mut digits = String::new();
+            while digits.len() < 2 && self.peek().is_ascii_hexdigit() {
+                digits.push(self.advance());
+            }
+            if digits.len() < 2 {
+                None
+            } else {
+                u32::from_str_radix(&digits, 16).ok()
+            }
+        } else if self.matches('{') {
+            let mut digits = String::new();
+            while self.peek().is_ascii_hexdigit() {
+                digits.push(self.advance());
+            }
+            if self.matches('}') && (1..=6).contains(&digits.len()) {
+                u32::from_str_radix(&digits, 16).ok()
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        return match value.and_then(char::from_u32) {
+            Some(c) => Ok(c),
+            None => Err(Error::new(
+                self.line,
+                format!(
+                    "Invalid escape sequence '{}'",
+                    self.slice(start, self.current)
+                ),
+            )),
+        };
+    }
+
+    fn add_string_token(
+        &mut self,
+        token: TokenType,
+        string: String,
+        length: usize,
+    ) -> Result<(), Error> {
+        if length > self.max_string_length {
+            return Err(Error::new(self.line, "String literal too long".to_string()));
+        }
+
+        self.add_token_literal(token, Some(Literal::String(string)));
+        return Ok(());
     }
 
-    fn number(&mut self) {
-        while self.peek().is_digit(10) {
+    fn number(&mut self) -> Result<(), Error> {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == self.decimal_separator && self.peek_next().is_ascii_digit() {
             self.advance();
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
 
-        let value: String = self.source[self.start..self.current].iter().collect();
-        let number = value.parse::<f64>().unwrap();
-        self.add_token_literal(TokenType::Number, Some(Literal::Number(number)));
-    }
-
+        let digits = self.source[self.start..self.current]
+            .iter()
+            .filter(|c| c.is_ascii_digit())
+            .count();
+        if digits > self.max_number_digits {
+            return Err(Error::new(self.line, "Number literal too long".to_string()));
+        }
+
+        let value = self
+            .slice(self.start, self.current)
+            .replace(self.decimal_separator, ".");
+        let number = match value.parse::<f64>() {
+            Ok(number) => number,
+            Err(_) => return Err(Error::new(self.line, "Invalid number literal".to_string())),
+        };
+
+        // Only a warning, so it doesn't stop the token being added.
+        if number.is_infinite() {
+            self.errors.push(Error::warning(
+                self.line,
+                "Number literal is too large and rounds to infinity".to_string(),
+            ));
+        }
+        self.add_token_literal(TokenType::Number, Some(Literal::Number(number)));
+        return Ok(());
+    }
+
     fn is_alpha(&self, c: char) -> bool {
         return c.is_alphabetic() || c == '_';
     }
 
+    fn attribute(&mut self) {
+        if !self.is_alpha(self.peek()) {
+            self.errors.push(Error::new(
+                self.line,
+                "Expected attribute name after '@'".to_string(),
+            ));
+            return;
+        }
+
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+
+        let name = self.slice(self.start + 1, self.current);
+        self.add_token_literal(TokenType::Attribute, Some(Literal::Identifier(name)));
+    }
+
     fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
 
-        let value = String::from_iter(&self.source[self.start..self.current]);
+        let value = self.slice(self.start, self.current);
+        let lookup = if self.case_insensitive {
+            value.to_lowercase()
+        } else {
+            value.clone()
+        };
 
-        if let Some(keyword) = self.keywords.get::<str>(&*value) {
-            self.add_token_literal(keyword.clone(), Some(Literal::Identifier(value)))
+        if let Some(keyword) = self.keywords.get::<str>(&lookup) {
+            self.add_token_literal(*keyword, Some(Literal::Identifier(value)))
         } else {
             self.add_token(TokenType::Identifier);
         }
@@ -304,6 +1258,8 @@ impl Scanner {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helper::format_errors;
+    use std::collections::HashSet;
 
     #[test]
     fn scan_tokens_with_error_test() {
@@ -338,7 +1294,7 @@ mod tests {
             TokenType::LessEqual,
             TokenType::Number,
             TokenType::RightParen,
-            TokenType::LeftBrace, 
+            TokenType::LeftBrace,
             TokenType::Identifier,
             TokenType::Equal,
             TokenType::Identifier,
@@ -360,17 +1316,709 @@ mod tests {
         // 2 extra lines for empty first/last line in string
         assert_eq!(scanner.line, 7);
 
-        let scanner_tokens_mapped: Vec<TokenType> = scanner
-            .tokens
-            .iter()
-            .map(|t| t.token_type.clone())
-            .collect();
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
 
         assert_eq!(scanner_tokens_mapped, expected);
 
         assert!(scanner.is_at_end());
     }
 
+    #[test]
+    fn scan_tokens_skips_shebang_test() {
+        let source = "#!/usr/bin/env lox\nprint \"Hello\";".to_string();
+
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Print);
+        assert_eq!(scanner.tokens[0].line, 2);
+    }
+
+    #[test]
+    fn scan_tokens_hash_after_start_test() {
+        let source = "var a = 1; #!".to_string();
+
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn errors_accessor_test() {
+        let source = "var $a = 1; var b = #;".to_string();
+
+        let mut scanner = Scanner::new(&source);
+        assert!(scanner.errors().is_empty());
+
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.errors().len(), 2);
+        assert_eq!(scanner.errors()[1].line(), 1);
+        assert_eq!(scanner.tokens.len(), 10);
+        assert_eq!(scanner.into_errors().len(), 2);
+    }
+
+    #[test]
+    fn scan_tokens_unterminated_string_test() {
+        let source = "var a = \"never closed".to_string();
+
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(scanner.tokens.last().unwrap().token_type, TokenType::EOF);
+    }
+
+    #[test]
+    fn scan_tokens_unterminated_multi_line_string_test() {
+        let source = "var a = 1;\nvar b = \"oops;\nvar c = 2;\n".to_string();
+
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].reason(), "Unterminated String");
+        assert_eq!(errors[0].line(), 2);
+    }
+
+    #[test]
+    fn scan_tokens_never_panics_test() {
+        // A fixed pool of interesting characters: token starts, multi-byte
+        // UTF-8, control characters and the characters that open literals.
+        let pool = [
+            '(', ')', '{', '}', ',', '.', '-', '+', ';', '*', '/', '!', '=', '<', '>', '"', '#',
+            '0', '9', 'a', 'z', '_', ' ', '\t', '\r', '\n', '\0', '\u{7}', '\u{1b}', '\u{7f}', 'é',
+            'ß', '€', '漢', '😀', '\u{200b}', '\u{fffd}',
+        ];
+
+        // xorshift64 keeps the corpus deterministic without pulling in a crate.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..5000 {
+            let len = (next() % 64) as usize;
+            let source: String = (0..len)
+                .map(|_| {
+                    let roll = next();
+                    if roll % 8 == 0 {
+                        char::from_u32((roll >> 8) as u32 % 0x11_0000).unwrap_or('\u{fffd}')
+                    } else {
+                        pool[(roll >> 8) as usize % pool.len()]
+                    }
+                })
+                .collect();
+
+            let mut scanner = Scanner::new(&source);
+            scanner.scan_tokens();
+
+            assert_eq!(scanner.tokens.last().unwrap().token_type, TokenType::EOF);
+        }
+    }
+
+    #[test]
+    fn token_type_hash_set_test() {
+        let set: HashSet<TokenType> = HashSet::from([
+            TokenType::SemiColon,
+            TokenType::RightBrace,
+            TokenType::Var,
+            TokenType::SemiColon,
+        ]);
+
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&TokenType::Var));
+        assert!(set.contains(&TokenType::RightBrace));
+        assert!(!set.contains(&TokenType::LeftBrace));
+    }
+
+    #[test]
+    fn scan_tokens_star_star_test() {
+        let source = "2 ** 3 * 4".to_string();
+
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            scanner_tokens_mapped,
+            [
+                TokenType::Number,
+                TokenType::StarStar,
+                TokenType::Number,
+                TokenType::Star,
+                TokenType::Number,
+                TokenType::EOF,
+            ]
+        );
+        assert_eq!(scanner.tokens[1].lexeme, "**");
+    }
+
+    #[test]
+    fn dump_tokens_test() {
+        let source = "var pi = 11.234;\nprint \"pi\";".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        assert_eq!(
+            dump_tokens(scanner.tokens()),
+            "Var \"var\" var @1
+Identifier \"pi\" @1
+Equal \"=\" @1
+Number \"11.234\" 11.234 @1
+SemiColon \";\" @1
+Print \"print\" print @2
+String \"\\\"pi\\\"\" \"pi\" @2
+SemiColon \";\" @2
+EOF \"\" @2
+"
+        );
+    }
+
+    #[test]
+    fn string_unterminated_returns_err_test() {
+        let mut scanner = Scanner::new("\"never closed");
+        scanner.advance();
+
+        let error = scanner.string().unwrap_err();
+        assert_eq!(error.reason(), "Unterminated String");
+        assert_eq!(error.line(), 1);
+        assert!(scanner.errors().is_empty());
+        assert!(scanner.tokens().is_empty());
+    }
+
+    #[test]
+    fn string_escapes_test() {
+        let source = "\"\\x41\\u{1F600}\\u{e9}\\n\"";
+        let mut scanner = Scanner::new(source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        // Other backslashes are kept as written.
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::String("A😀é\\n".to_string()))
+        );
+        assert_eq!(scanner.tokens[0].lexeme, source);
+    }
+
+    #[test]
+    fn string_malformed_escapes_test() {
+        for (escape, shown) in [
+            ("\\xZZ", "\\x"),
+            ("\\x4", "\\x4"),
+            ("\\u{110000}", "\\u{110000}"),
+            ("\\u{D800}", "\\u{D800}"),
+            ("\\u{}", "\\u{}"),
+            ("\\u{41", "\\u{41"),
+            ("\\u41", "\\u"),
+        ] {
+            let source = format!("\"a{}z\\x41\" + 1;", escape);
+            let mut scanner = Scanner::new(&source);
+            let errors = scanner.scan_tokens();
+
+            assert_eq!(errors.len(), 1, "{}", escape);
+            assert_eq!(
+                errors[0].reason(),
+                format!("Invalid escape sequence '{}'", shown)
+            );
+            // The string is still consumed up to its closing quote.
+            let types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.token_type).collect();
+            assert_eq!(
+                types,
+                vec![
+                    TokenType::Plus,
+                    TokenType::Number,
+                    TokenType::SemiColon,
+                    TokenType::EOF
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn string_too_long_returns_err_test() {
+        let mut scanner = Scanner::new("\"abcdef\"");
+        scanner.advance();
+        scanner.set_max_string_length(3);
+
+        let error = scanner.string().unwrap_err();
+        assert_eq!(error.reason(), "String literal too long");
+    }
+
+    #[test]
+    fn number_too_long_returns_err_test() {
+        let mut scanner = Scanner::new("123456");
+        scanner.set_max_number_digits(4);
+
+        let error = scanner.number().unwrap_err();
+        assert_eq!(error.reason(), "Number literal too long");
+        assert!(scanner.tokens().is_empty());
+    }
+
+    #[test]
+    fn token_stream_test() {
+        let mut scanner = Scanner::new("var a = b; // c\nprint a;");
+        scanner.set_keep_comments(true);
+        scanner.scan_tokens();
+        let stream = scanner.into_tokens().filter_comments();
+
+        assert!(stream.iter().all(|t| t.token_type != TokenType::Comment));
+
+        let mut identifiers = Vec::new();
+        for token in stream {
+            if token.token_type == TokenType::Identifier {
+                identifiers.push(token.lexeme);
+            }
+        }
+        assert_eq!(identifiers, vec!["a", "b", "a"]);
+    }
+
+    #[test]
+    fn scanner_builder_test() {
+        let builder = ScannerBuilder::new()
+            .case_insensitive(true)
+            .keep_comments(true);
+        let mut scanner = builder.build("VAR a; // note");
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Var);
+        assert_eq!(scanner.tokens[3].token_type, TokenType::Comment);
+
+        // The builder can be reused.
+        let mut scanner = builder.build("If");
+        scanner.scan_tokens();
+        assert_eq!(scanner.tokens[0].token_type, TokenType::If);
+    }
+
+    #[test]
+    fn slice_test() {
+        let scanner = Scanner::new("print \"héllo\";");
+
+        assert_eq!(scanner.source().len(), 14);
+        assert_eq!(scanner.slice(7, 12), "héllo");
+        assert_eq!(scanner.slice(7, 100), "héllo\";");
+        assert_eq!(scanner.slice(100, 200), "");
+        assert_eq!(scanner.slice(5, 2), "");
+    }
+
+    #[test]
+    fn to_source_test() {
+        let mut scanner = Scanner::new("var x=1;");
+        scanner.scan_tokens();
+
+        assert_eq!(to_source(scanner.tokens()), "var x = 1;");
+    }
+
+    #[test]
+    fn to_source_normalizes_test() {
+        let source = "PRINT -a*(b+f(1 ,2))!=! c.d;\nvar s=\"n: ${ n-1 }!\";  // done\nx=-1;";
+        let mut scanner = Scanner::new(source);
+        scanner.set_case_insensitive(true);
+        scanner.set_keep_comments(true);
+        scanner.scan_tokens();
+
+        let normalized = to_source(scanner.tokens());
+        assert_eq!(
+            normalized,
+            "print -a * (b + f(1, 2)) != !c.d; var s = \"n: ${n - 1}!\"; // done\nx = -1;"
+        );
+
+        // Normalizing again changes nothing.
+        let mut scanner = Scanner::new(&normalized);
+        scanner.set_keep_comments(true);
+        scanner.scan_tokens();
+        assert_eq!(to_source(scanner.tokens()), normalized);
+    }
+
+    #[test]
+    fn scan_tokens_skips_comments_test() {
+        let source = "var a = 1; // the answer\n".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.tokens.len(), 6);
+        assert!(scanner
+            .tokens
+            .iter()
+            .all(|t| t.token_type != TokenType::Comment));
+    }
+
+    #[test]
+    fn scan_tokens_keep_comments_test() {
+        let source = "var a = 1; // the answer\nprint a;".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.set_keep_comments(true);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+
+        let comment = &scanner.tokens[5];
+        assert_eq!(comment.token_type, TokenType::Comment);
+        assert_eq!(comment.lexeme, "// the answer");
+        assert_eq!(
+            comment.literal,
+            Some(Literal::String(" the answer".to_string()))
+        );
+        assert_eq!(comment.line, 1);
+        assert_eq!(scanner.tokens[6].token_type, TokenType::Print);
+    }
+
+    #[test]
+    fn scan_tokens_switch_test() {
+        let source = "switch (a) { case 1: print a; default: print b; }".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            scanner_tokens_mapped,
+            [
+                TokenType::Switch,
+                TokenType::LeftParen,
+                TokenType::Identifier,
+                TokenType::RightParen,
+                TokenType::LeftBrace,
+                TokenType::Case,
+                TokenType::Number,
+                TokenType::Colon,
+                TokenType::Print,
+                TokenType::Identifier,
+                TokenType::SemiColon,
+                TokenType::Default,
+                TokenType::Colon,
+                TokenType::Print,
+                TokenType::Identifier,
+                TokenType::SemiColon,
+                TokenType::RightBrace,
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_tokens_do_while_test() {
+        let source = "do { a = a + 1; } while (a < 10);".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Do);
+        assert_eq!(scanner.tokens[9].token_type, TokenType::While);
+    }
+
+    #[test]
+    fn number_literal_display_test() {
+        assert_eq!(
+            Literal::Number(0.1 + 0.2).to_string(),
+            "0.30000000000000004"
+        );
+        assert_eq!(Literal::Number(3.0).to_string(), "3");
+        assert_eq!(Literal::Number(-2.5).to_string(), "-2.5");
+        assert_eq!(Literal::Number(1e21).to_string(), "1000000000000000000000");
+    }
+
+    #[test]
+    fn eof_line_test() {
+        for source in ["a", "a\n", "a\n\n// trailing comment\n"] {
+            let mut scanner = Scanner::new(source);
+            scanner.scan_tokens();
+
+            assert_eq!(scanner.tokens.last().unwrap().line, 1);
+        }
+
+        let mut scanner = Scanner::new("a\n\"multi\nline\"\n");
+        scanner.scan_tokens();
+        assert_eq!(scanner.tokens.last().unwrap().line, 3);
+
+        let mut scanner = Scanner::new("\n\n");
+        scanner.scan_tokens();
+        assert_eq!(scanner.tokens.last().unwrap().line, 1);
+    }
+
+    #[test]
+    fn scan_tokens_custom_comment_prefix_test() {
+        let source = "x = 1 # comment\ny = 2 / 1 // not a comment".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.set_comment_prefix("#");
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            scanner_tokens_mapped,
+            [
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Number,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Number,
+                TokenType::Slash,
+                TokenType::Number,
+                TokenType::Slash,
+                TokenType::Slash,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_tokens_keep_custom_comment_test() {
+        let source = "-- note\nx".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.set_comment_prefix("--");
+        scanner.set_keep_comments(true);
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Comment);
+        assert_eq!(scanner.tokens[0].lexeme, "-- note");
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::String(" note".to_string()))
+        );
+        assert_eq!(scanner.tokens[1].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn scan_tokens_tabs_test() {
+        let source = "\ttrue\tthis".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::True);
+        assert_eq!(scanner.tokens[1].token_type, TokenType::This);
+    }
+
+    #[test]
+    fn mixed_indentation_test() {
+        let source = "if (a) {\n\t print a;\n\tprint b;\n    print c;\n}".to_string();
+
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+        assert_eq!(errors.len(), 0);
+
+        let mut scanner = Scanner::new(&source);
+        scanner.set_warn_mixed_indentation(true);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].is_warning());
+        assert_eq!(errors[0].line(), 2);
+        assert_eq!(scanner.tokens.len(), 16);
+    }
+
+    #[test]
+    fn scan_tokens_empty_program_test() {
+        for source in ["", "  \t\r\n\n ", "// only a comment\n// and another"] {
+            let mut scanner = Scanner::new(source);
+            let errors = scanner.scan_tokens();
+
+            assert_eq!(errors.len(), 0);
+            assert_eq!(scanner.tokens.len(), 1);
+            assert_eq!(scanner.tokens[0].token_type, TokenType::EOF);
+        }
+    }
+
+    #[test]
+    fn token_type_classification_test() {
+        assert!(TokenType::While.is_keyword());
+        assert!(TokenType::Nil.is_keyword());
+        assert!(!TokenType::Identifier.is_keyword());
+
+        assert!(TokenType::Number.is_literal());
+        assert!(TokenType::True.is_literal());
+        assert!(!TokenType::Identifier.is_literal());
+        assert!(!TokenType::Var.is_literal());
+
+        assert!(TokenType::Plus.is_binary_operator());
+        assert!(TokenType::LessEqual.is_binary_operator());
+        assert!(TokenType::Or.is_binary_operator());
+        assert!(!TokenType::Bang.is_binary_operator());
+        assert!(!TokenType::Equal.is_binary_operator());
+
+        assert!(TokenType::Var.is_statement_start());
+        assert!(TokenType::Return.is_statement_start());
+        assert!(!TokenType::Else.is_statement_start());
+        assert!(!TokenType::SemiColon.is_statement_start());
+    }
+
+    fn assert_rescan_matches(source: &str, edit_start: usize, edit_len: usize, new_text: &str) {
+        let builder = ScannerBuilder::new().warn_mixed_indentation(true);
+        assert_rescans_match(&builder, source, &[(edit_start, edit_len, new_text)]);
+    }
+
+    // Applies the edits in turn to one scanner, checking it against a full
+    // scan of the edited source after each.
+    fn assert_rescans_match(
+        builder: &ScannerBuilder,
+        source: &str,
+        edits: &[(usize, usize, &str)],
+    ) {
+        let mut scanner = builder.build(source);
+        scanner.scan_tokens();
+
+        let mut edited: Vec<char> = source.chars().collect();
+        for (count, &(edit_start, edit_len, new_text)) in edits.iter().enumerate() {
+            scanner.rescan_range(edit_start, edit_len, new_text);
+
+            let edit_start = edit_start.min(edited.len());
+            let edit_end = (edit_start + edit_len).min(edited.len());
+            edited.splice(edit_start..edit_end, new_text.chars());
+            let source: String = edited.iter().collect();
+            let mut expected = builder.build(&source);
+            expected.scan_tokens();
+
+            let context = format!("{:?} after edits {:?}", source, &edits[..=count]);
+            assert_eq!(
+                dump_tokens(&scanner.tokens),
+                dump_tokens(&expected.tokens),
+                "{}",
+                context
+            );
+            assert_eq!(
+                scanner
+                    .tokens
+                    .iter()
+                    .map(|t| t.offset)
+                    .collect::<Vec<usize>>(),
+                expected
+                    .tokens
+                    .iter()
+                    .map(|t| t.offset)
+                    .collect::<Vec<usize>>(),
+                "{}",
+                context
+            );
+            assert_eq!(
+                format_errors(&scanner.errors, &source, false),
+                format_errors(&expected.errors, &source, false),
+                "{}",
+                context
+            );
+            assert_eq!(scanner.line, expected.line, "{}", context);
+        }
+    }
+
+    #[test]
+    fn rescan_range_test() {
+        let source = "var a = 1;\nvar b = a + 2;\nprint b;\n";
+
+        // `+` becomes `-` in the middle of the second line.
+        assert_rescan_matches(source, 21, 1, "-");
+        // Joining two lines shifts every later token up a line.
+        assert_rescan_matches(source, 10, 1, " ");
+        // Opening a string swallows the rest of the file.
+        assert_rescan_matches(source, 19, 0, "\"");
+        // Edits touching the start and the end of the source.
+        assert_rescan_matches(source, 0, 3, "print");
+        assert_rescan_matches(source, source.len(), 0, "print 3;");
+        // Removing a newline before an unterminated string moves its error.
+        assert_rescan_matches("a\nb \"oops", 1, 1, "");
+    }
+
+    #[test]
+    fn rescan_range_repeated_edits_test() {
+        let builder = ScannerBuilder::new();
+        assert_rescans_match(&builder, "(0V\"${", &[(1, 2, "VAR**"), (7, 2, "\"")]);
+
+        let builder = ScannerBuilder::new()
+            .comment_prefix("#")
+            .keep_comments(true);
+        let source = ";.+,é-\"${VAR}\nVAR";
+        assert_rescans_match(&builder, source, &[(0, 1, "x"), (17, 2, "")]);
+    }
+
+    #[test]
+    fn rescan_range_shifts_later_tokens_test() {
+        let source = "var a = 1;\nvar b = a + 2;\nprint b;\n";
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        scanner.rescan_range(21, 1, "*\n");
+
+        assert_eq!(scanner.tokens[9].token_type, TokenType::Star);
+        assert_eq!(scanner.tokens[12].token_type, TokenType::Print);
+        assert_eq!(scanner.tokens[12].line, 4);
+        assert_eq!(scanner.tokens[12].offset, 27);
+    }
+
+    #[test]
+    fn rescan_range_random_edits_test() {
+        let sources = [
+            "var a = 1;\nvar b = a + 2;\nprint b;\n",
+            "fun f(x) {\n  return \"x=${x + 1}\";\n}\n// done\nprint f(2.5);",
+            "if (a <= 10) {\n\t a = a ** 2; $\n}\n\"multi\nline\" @test",
+            "print \"a${ \"b${c}\" }d\";\nvar e = 123.456;",
+            "x;\n\ny \"open\nstring",
+        ];
+        let replacements = [
+            "", "\"", "${", "}", "\n", "\t ", "1.5", "//", "x", "$", "@a",
+        ];
+
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+
+        for source in sources {
+            let len = source.chars().count();
+            for _ in 0..500 {
+                let edit_start = next() % (len + 1);
+                let edit_len = next() % 4;
+                let new_text = replacements[next() % replacements.len()];
+                assert_rescan_matches(source, edit_start, edit_len, new_text);
+            }
+        }
+
+        // Chains of edits on one scanner, as an editor makes them.
+        let builders = [
+            ScannerBuilder::new(),
+            ScannerBuilder::new()
+                .comment_prefix("#")
+                .keep_comments(true),
+        ];
+        for builder in &builders {
+            for source in sources {
+                for _ in 0..300 {
+                    let mut len = source.chars().count();
+                    let mut edits = Vec::new();
+                    for _ in 0..1 + next() % 4 {
+                        let edit_start = next() % (len + 1);
+                        let edit_len = next() % 4;
+                        let new_text = replacements[next() % replacements.len()];
+                        let removed = edit_len.min(len - edit_start);
+                        len = len - removed + new_text.chars().count();
+                        edits.push((edit_start, edit_len, new_text));
+                    }
+                    assert_rescans_match(builder, source, &edits);
+                }
+            }
+        }
+    }
+
     #[test]
     fn peek_and_advance_test() {
         let source = "var test = 1234".to_string();
@@ -395,7 +2043,7 @@ mod tests {
 
         //drop the \" first as that's how scan token would handle it
         scanner.advance();
-        scanner.string();
+        scanner.string().unwrap();
 
         assert_eq!(scanner.tokens.len(), 1);
 
@@ -405,14 +2053,152 @@ mod tests {
             string.literal,
             Some(Literal::String("Hello \n World".to_string()))
         );
+        assert_eq!(string.line, 1);
+        assert_eq!(scanner.line(), 2);
+    }
+
+    #[test]
+    fn multi_line_string_start_line_test() {
+        let source = "var a;\nprint \"one\ntwo\nthree\"; print a;".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        let string = &scanner.tokens[4];
+        assert_eq!(string.token_type, TokenType::String);
         assert_eq!(string.line, 2);
+        assert_eq!(scanner.tokens[6].line, 4);
+    }
+
+    #[test]
+    fn line_of_test() {
+        let source = "a\nbc\n\nd".to_string();
+        let scanner = Scanner::new(&source);
+
+        assert_eq!(scanner.line_of(0), 1);
+        assert_eq!(scanner.line_of(1), 1);
+        assert_eq!(scanner.line_of(2), 2);
+        assert_eq!(scanner.line_of(4), 2);
+        assert_eq!(scanner.line_of(5), 3);
+        assert_eq!(scanner.line_of(6), 4);
+        assert_eq!(scanner.line_of(100), 4);
+    }
+
+    #[test]
+    fn scan_tokens_interpolation_test() {
+        let source = "\"sum=${1+2}\"".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            scanner_tokens_mapped,
+            [
+                TokenType::Interpolation,
+                TokenType::Number,
+                TokenType::Plus,
+                TokenType::Number,
+                TokenType::String,
+                TokenType::EOF,
+            ]
+        );
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::String("sum=".to_string()))
+        );
+        assert_eq!(
+            scanner.tokens[4].literal,
+            Some(Literal::String("".to_string()))
+        );
+    }
+
+    #[test]
+    fn scan_tokens_nested_interpolation_test() {
+        let source = "\"a${ \"b${c}\" }d\"".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            scanner_tokens_mapped,
+            [
+                TokenType::Interpolation,
+                TokenType::Interpolation,
+                TokenType::Identifier,
+                TokenType::String,
+                TokenType::String,
+                TokenType::EOF,
+            ]
+        );
+        assert_eq!(
+            scanner.tokens[4].literal,
+            Some(Literal::String("d".to_string()))
+        );
+    }
+
+    #[test]
+    fn scan_tokens_escaped_interpolation_test() {
+        let source = "\"cost \\${x} in $\"".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        assert_eq!(scanner.tokens.len(), 2);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::String);
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::String("cost ${x} in $".to_string()))
+        );
+    }
+
+    #[test]
+    fn scan_tokens_unterminated_interpolation_test() {
+        let source = "\"a${1 + 2".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn string_length_limit_test() {
+        let source = "\"12345\" \"123456\"".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.set_max_string_length(5);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(scanner.tokens.len(), 2);
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::String("12345".to_string()))
+        );
+    }
+
+    #[test]
+    fn number_digit_limit_test() {
+        let source = "12.34 123.45".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.set_max_number_digits(4);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(scanner.tokens.len(), 2);
+        assert_eq!(scanner.tokens[0].literal, Some(Literal::Number(12.34)));
     }
 
     #[test]
     fn create_double_test() {
         let source = "11.234".to_string();
         let mut scanner = Scanner::new(&source);
-        scanner.number();
+        scanner.number().unwrap();
 
         assert_eq!(scanner.tokens.len(), 1);
 
@@ -426,7 +2212,7 @@ mod tests {
     fn create_number_test() {
         let source = "10".to_string();
         let mut scanner = Scanner::new(&source);
-        scanner.number();
+        scanner.number().unwrap();
 
         assert_eq!(scanner.tokens.len(), 1);
 
@@ -436,6 +2222,109 @@ mod tests {
         assert_eq!(number.line, 1);
     }
 
+    #[test]
+    fn create_huge_number_test() {
+        let source = format!("1{}", "0".repeat(400));
+        let mut scanner = Scanner::new(&source);
+        scanner.number().unwrap();
+
+        assert_eq!(scanner.errors.len(), 1);
+        assert!(scanner.errors[0].is_warning());
+
+        let number = &scanner.tokens[0];
+        assert_eq!(number.token_type, TokenType::Number);
+        assert_eq!(number.literal, Some(Literal::Number(f64::INFINITY)));
+    }
+
+    #[test]
+    fn create_large_number_test() {
+        let source = "123456789012345678901234567890".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.number().unwrap();
+
+        assert_eq!(scanner.errors.len(), 0);
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::Number(123456789012345678901234567890.0))
+        );
+    }
+
+    #[test]
+    fn scan_tokens_attribute_test() {
+        let source = "@test\nfun check() {}".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+
+        let attribute = &scanner.tokens[0];
+        assert_eq!(attribute.token_type, TokenType::Attribute);
+        assert_eq!(attribute.lexeme, "@test");
+        assert_eq!(
+            attribute.literal,
+            Some(Literal::Identifier("test".to_string()))
+        );
+        assert_eq!(scanner.tokens[1].token_type, TokenType::Fun);
+    }
+
+    #[test]
+    fn scan_tokens_bare_at_test() {
+        let source = "@ 1".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn default_decimal_separator_test() {
+        let source = "3,14".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens();
+
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            scanner_tokens_mapped,
+            [
+                TokenType::Number,
+                TokenType::Comma,
+                TokenType::Number,
+                TokenType::EOF
+            ]
+        );
+        assert_eq!(scanner.tokens[0].literal, Some(Literal::Number(3.0)));
+    }
+
+    #[test]
+    fn comma_decimal_separator_test() {
+        let source = "f(2,5, 2)".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.set_decimal_separator(',');
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+
+        let scanner_tokens_mapped: Vec<TokenType> =
+            scanner.tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            scanner_tokens_mapped,
+            [
+                TokenType::Identifier,
+                TokenType::LeftParen,
+                TokenType::Number,
+                TokenType::Comma,
+                TokenType::Number,
+                TokenType::RightParen,
+                TokenType::EOF
+            ]
+        );
+        assert_eq!(scanner.tokens[2].lexeme, "2,5");
+        assert_eq!(scanner.tokens[2].literal, Some(Literal::Number(2.5)));
+    }
+
     #[test]
     fn create_keyword_test() {
         let source = "var".to_string();
@@ -467,6 +2356,85 @@ mod tests {
         assert_eq!(identifier.line, 1);
     }
 
+    #[test]
+    fn case_sensitive_keyword_test() {
+        let source = "VAR".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.identifier();
+
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn case_insensitive_keyword_test() {
+        let source = "VAR myVar = 1; IF (myVar) {}".to_string();
+        let mut scanner = Scanner::new(&source);
+        scanner.set_case_insensitive(true);
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Var);
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::Identifier("VAR".to_string()))
+        );
+        assert_eq!(scanner.tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(scanner.tokens[1].lexeme, "myVar");
+        assert_eq!(scanner.tokens[5].token_type, TokenType::If);
+    }
+
+    #[test]
+    fn scan_tokens_is_test() {
+        let source = "print pet is Animal;".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        assert_eq!(scanner.tokens[2].token_type, TokenType::Is);
+        assert_eq!(scanner.tokens[3].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn shared_keywords_test() {
+        for index in 0..1000 {
+            let source = format!("var a{} = nil;", index);
+            let mut scanner = Scanner::new(&source);
+            scanner.scan_tokens();
+
+            assert!(matches!(scanner.keywords, Cow::Borrowed(_)));
+            assert_eq!(scanner.tokens[0].token_type, TokenType::Var);
+            assert_eq!(scanner.tokens[3].token_type, TokenType::Nil);
+        }
+    }
+
+    #[test]
+    fn custom_keywords_test() {
+        let mut keywords = default_keywords();
+        keywords.remove("fun");
+        keywords.insert("function", TokenType::Fun);
+
+        let source = "function add(a, b) {} fun".to_string();
+        let mut scanner = Scanner::with_keywords(&source, keywords);
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Fun);
+        assert_eq!(scanner.tokens[0].lexeme, "function");
+        assert_eq!(scanner.tokens[9].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn create_underscore_identifier_test() {
+        let source = "var _ = my_var;".to_string();
+        let mut scanner = Scanner::new(&source);
+        let errors = scanner.scan_tokens();
+
+        assert_eq!(errors.len(), 0);
+        assert_eq!(scanner.tokens.len(), 6);
+        assert_eq!(scanner.tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(scanner.tokens[1].lexeme, "_");
+        assert_eq!(scanner.tokens[3].token_type, TokenType::Identifier);
+        assert_eq!(scanner.tokens[3].lexeme, "my_var");
+    }
+
     #[test]
     fn is_at_end_test() {
         let source = "Hello world".to_string();
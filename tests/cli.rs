@@ -0,0 +1,213 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn lox() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_crafting_interpreters"))
+}
+
+fn write_script(name: &str, contents: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("lox_cli_{}_{}", std::process::id(), name));
+    fs::write(&path, contents).expect("Unable to write test script");
+    return path;
+}
+
+fn run(args: &[&str]) -> Output {
+    lox().args(args).output().expect("Unable to run lox")
+}
+
+fn run_with_stdin(args: &[&str], input: &[u8]) -> Output {
+    let mut child = lox()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Unable to run lox");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    return child.wait_with_output().expect("Unable to run lox");
+}
+
+#[test]
+fn check_valid_file_test() {
+    let path = write_script("check_valid.lox", b"var a = 1;\nprint a;\n");
+    let output = run(&["--check", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn check_invalid_file_test() {
+    let path = write_script("check_invalid.lox", b"var a = $;\nprint \"unterminated;\n");
+    let output = run(&["--check", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "[Line 1 ] var a = $;\n    Error: Unexpected Character\n\
+         [Line 2 ] print \"unterminated;\n    Error: Unterminated String\n"
+    );
+}
+
+#[test]
+fn no_color_test() {
+    let path = write_script("no_color.lox", b"var a = $;\n");
+    let output = run(&["--no-color", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[Line 1 ] var a = $;\n    Error: Unexpected Character\n"
+    );
+}
+
+#[test]
+fn missing_file_test() {
+    let path = env::temp_dir().join("lox_cli_does_not_exist.lox");
+    let output = run(&[path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(66));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: cannot read '"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn non_utf8_file_test() {
+    let path = write_script("binary.lox", &[0x70, 0x72, 0xff, 0xfe, 0x00, 0xc3]);
+    let output = run(&[path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(66));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("valid UTF-8"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn count_test() {
+    let path = write_script("count.lox", b"var a = 1;\n// comment\nprint a;\n");
+    let output = run(&["--count", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "8 tokens, 3 lines\n"
+    );
+}
+
+#[test]
+fn help_test() {
+    for flag in ["-h", "--help"] {
+        let output = run(&[flag]);
+
+        assert_eq!(output.status.code(), Some(0));
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("Usage: lox"));
+    }
+}
+
+#[test]
+fn unknown_flag_test() {
+    let output = run(&["--frobnicate"]);
+
+    assert_eq!(output.status.code(), Some(64));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: unknown flag '--frobnicate'"));
+}
+
+#[test]
+fn stdin_dash_test() {
+    let output = run_with_stdin(&["--count", "-"], b"var a = 1;\nprint a;\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "8 tokens, 2 lines\n"
+    );
+}
+
+#[test]
+fn piped_stdin_test() {
+    let output = run_with_stdin(&[], b"var a = 1;\nprint a;\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn piped_stdin_with_errors_test() {
+    let output = run_with_stdin(&["--no-color"], b"var a = 1;\nprint $a;\n");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[Line 2 ] print $a;\n    Error: Unexpected Character\n"
+    );
+}
+
+#[test]
+fn empty_program_test() {
+    let sources: [&[u8]; 3] = [b"", b"  \n\t\n", b"// nothing to see here\n"];
+    for (index, source) in sources.iter().enumerate() {
+        let path = write_script(&format!("empty_{}.lox", index), source);
+        let output = run(&[path.to_str().unwrap()]);
+
+        assert_eq!(output.status.code(), Some(0));
+        assert!(output.stdout.is_empty());
+        assert!(output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn fmt_test() {
+    let path = write_script("fmt.lox", b"var a=1;if(a){print a;}\n");
+    let output = run(&["--fmt", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "var a = 1;\nif (a) {\n    print a;\n}\n"
+    );
+}
+
+#[test]
+fn fmt_keeps_shebang_test() {
+    let output = run_with_stdin(&["--fmt", "-"], b"#!/usr/bin/env lox\nprint 1 ;\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#!/usr/bin/env lox\nprint 1;\n"
+    );
+}
+
+#[test]
+fn fmt_invalid_file_test() {
+    let path = write_script("fmt_invalid.lox", b"var a = $;\n");
+    let output = run(&["--fmt", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "[Line 1 ] var a = $;\n    Error: Unexpected Character\n"
+    );
+}
+
+#[test]
+fn fmt_warning_goes_to_stderr_test() {
+    let source = format!("var big = 1{};\n", "0".repeat(400));
+    let path = write_script("fmt_warning.lox", source.as_bytes());
+    let output = run(&["--fmt", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), source);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Warning: Number literal is too large"));
+}